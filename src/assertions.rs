@@ -0,0 +1,92 @@
+use std::fmt::Write as _;
+
+#[track_caller]
+/// Compares `actual` (typically a command's stdout) against `template`
+/// using line-oriented, wildcard-tolerant matching instead of exact byte
+/// equality.
+///
+/// `[..]` in `template` matches any run of characters within that line.
+/// Panics with a readable diff on mismatch.
+pub fn assert_stdout_matches(actual: impl AsRef<[u8]>, template: impl AsRef<str>) {
+    assert_matches_with_redactions(actual, template, &[]);
+}
+
+#[track_caller]
+/// Like [`assert_stdout_matches`], but first substitutes each `(key,
+/// value)` pair into `template` (e.g. `("[ROOT]", tmp_dir)`) before
+/// comparing, so volatile values like sandbox paths can be named in the
+/// template.
+pub fn assert_matches_with_redactions(
+    actual: impl AsRef<[u8]>,
+    template: impl AsRef<str>,
+    redactions: &[(&str, &str)],
+) {
+    let actual = String::from_utf8_lossy(actual.as_ref());
+    let mut expected = template.as_ref().to_string();
+    for (key, value) in redactions {
+        expected = expected.replace(key, value);
+    }
+
+    let actual_lines: Vec<&str> = actual.lines().collect();
+    let expected_lines: Vec<&str> = expected.lines().collect();
+
+    let matches = actual_lines.len() == expected_lines.len()
+        && actual_lines
+            .iter()
+            .zip(expected_lines.iter())
+            .all(|(a, e)| lines_match(e, a));
+
+    if !matches {
+        panic!(
+            "stdout did not match template:\n{}",
+            unified_diff(&expected_lines, &actual_lines)
+        );
+    }
+}
+
+/// Matches `actual` against `expected`, where `[..]` in `expected` matches
+/// any run of characters within the line.
+fn lines_match(expected: &str, actual: &str) -> bool {
+    let parts: Vec<&str> = expected.split("[..]").collect();
+
+    let Some(mut remaining) = actual.strip_prefix(parts[0]) else {
+        return false;
+    };
+
+    let Some((last, middle)) = parts[1..].split_last() else {
+        return remaining.is_empty();
+    };
+
+    for part in middle {
+        match remaining.find(part) {
+            Some(pos) => remaining = &remaining[pos + part.len()..],
+            None => return false,
+        }
+    }
+
+    remaining.ends_with(last)
+}
+
+/// Renders a minimal unified-style diff between expected and actual lines.
+fn unified_diff(expected: &[&str], actual: &[&str]) -> String {
+    let mut diff = String::new();
+    for i in 0..expected.len().max(actual.len()) {
+        match (expected.get(i), actual.get(i)) {
+            (Some(e), Some(a)) if lines_match(e, a) => {
+                let _ = writeln!(diff, "  {a}");
+            }
+            (Some(e), Some(a)) => {
+                let _ = writeln!(diff, "- {e}");
+                let _ = writeln!(diff, "+ {a}");
+            }
+            (Some(e), None) => {
+                let _ = writeln!(diff, "- {e}");
+            }
+            (None, Some(a)) => {
+                let _ = writeln!(diff, "+ {a}");
+            }
+            (None, None) => {}
+        }
+    }
+    diff
+}
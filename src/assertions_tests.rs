@@ -0,0 +1,31 @@
+mod tests {
+    use crate::{assert_matches_with_redactions, assert_stdout_matches};
+
+    #[test]
+    fn exact_output_matches() {
+        assert_stdout_matches(b"On branch main\nnothing to commit", "On branch main\nnothing to commit");
+    }
+
+    #[test]
+    fn wildcard_tolerates_volatile_runs() {
+        assert_stdout_matches(
+            b"Compiling mockcmd v0.1.0 (/home/user/mockcmd)\nFinished in 0.42s",
+            "Compiling mockcmd v0.1.0 ([..])\nFinished in [..]s",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "stdout did not match template")]
+    fn mismatch_panics_with_diff() {
+        assert_stdout_matches(b"actual output", "expected output");
+    }
+
+    #[test]
+    fn redaction_keys_are_substituted_into_the_template() {
+        assert_matches_with_redactions(
+            b"wrote to /tmp/build-xyz/out.bin",
+            "wrote to [ROOT]/out.bin",
+            &[("[ROOT]", "/tmp/build-xyz")],
+        );
+    }
+}
@@ -2,36 +2,124 @@ use std::io;
 use std::process;
 
 use std::ffi::{OsStr, OsString};
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Mutex;
 
-use crate::CommandMockBuilder;
+use crate::{ArgMatcher, CommandMockBuilder, MockResponseFn, QueuedResponse};
 
 type Result<T = ()> = io::Result<T>;
 
+/// A registered mock paired with a call counter, used to walk its queue of
+/// `then_*` responses in call order. The counter only advances for mocks
+/// that have queued responses.
+struct RegisteredMock {
+    definition: MockDefinition,
+    call_count: AtomicUsize,
+}
+
 /// Stores for mock definitions
-static MOCK_REGISTRY: Mutex<Vec<MockDefinition>> = Mutex::new(Vec::new());
+static MOCK_REGISTRY: Mutex<Vec<RegisteredMock>> = Mutex::new(Vec::new());
 /// Stores executed commands
 static EXECUTED_COMMANDS: Mutex<Vec<ExecutedCommand>> = Mutex::new(Vec::new());
 
-pub fn find_mock(program: &OsString, args: &[OsString]) -> Option<MockDefinition> {
+pub fn find_mock(
+    program: &OsString,
+    args: &[OsString],
+    current_dir: Option<&OsString>,
+    env: &[(OsString, OsString)],
+) -> Option<MockDefinition> {
     let mocks = MOCK_REGISTRY.lock().unwrap();
-    for mock in mocks.iter() {
-        if &mock.program == program && mock.args == args {
-            return Some(mock.clone());
+    let registered = mocks.iter().find(|registered| {
+        let mock = &registered.definition;
+        &mock.program == program
+            && matches_args(&mock.args, args)
+            && mock
+                .current_dir
+                .as_ref()
+                .is_none_or(|expected| Some(expected) == current_dir)
+            && mock
+                .env
+                .iter()
+                .all(|(k, v)| env.iter().any(|(ek, ev)| ek == k && ev == v))
+    })?;
+
+    let mock = registered.definition.clone();
+    if mock.responses.is_empty() {
+        return Some(mock);
+    }
+
+    let call_index = registered.call_count.fetch_add(1, Ordering::SeqCst);
+    let step = mock.responses[call_index.min(mock.responses.len() - 1)].clone();
+
+    Some(MockDefinition {
+        exit_status: step.exit_status.or(mock.exit_status),
+        stdout: step.stdout.or(mock.stdout),
+        stderr: step.stderr.or(mock.stderr),
+        ..mock
+    })
+}
+
+/// Walks `matchers` against `args` position-by-position, returning whether
+/// every matcher is satisfied. A trailing `ArgMatcher::Rest` matches
+/// whatever arguments remain without being consumed one-by-one.
+fn matches_args(matchers: &[ArgMatcher], args: &[OsString]) -> bool {
+    let mut pos = 0;
+    for matcher in matchers {
+        match matcher {
+            ArgMatcher::Rest => return true,
+            ArgMatcher::Exact(expected) => match args.get(pos) {
+                Some(actual) if actual == expected => {}
+                _ => return false,
+            },
+            ArgMatcher::Any => {
+                if args.get(pos).is_none() {
+                    return false;
+                }
+            }
+            ArgMatcher::Regex(re) => match args.get(pos).and_then(|a| a.to_str()) {
+                Some(s) if re.is_match(s) => {}
+                _ => return false,
+            },
         }
+        pos += 1;
     }
-    None
+    pos == args.len()
 }
 
 /// Represents a definition for how a command should be mocked.
 // Rust sees some fields as unused but aren't
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct MockDefinition {
     pub program: OsString,
-    pub args: Vec<OsString>,
+    pub args: Vec<ArgMatcher>,
+    pub current_dir: Option<OsString>,
+    pub env: Vec<(OsString, OsString)>,
     pub exit_status: Option<i32>,
     pub stdout: Option<Vec<u8>>,
     pub stderr: Option<Vec<u8>>,
+    pub response: Option<MockResponseFn>,
+    pub error_kind: Option<io::ErrorKind>,
+    pub error_message: Option<String>,
+    pub responses: Vec<QueuedResponse>,
+}
+
+impl fmt::Debug for MockDefinition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MockDefinition")
+            .field("program", &self.program)
+            .field("args", &self.args)
+            .field("current_dir", &self.current_dir)
+            .field("env", &self.env)
+            .field("exit_status", &self.exit_status)
+            .field("stdout", &self.stdout)
+            .field("stderr", &self.stderr)
+            .field("response", &self.response.as_ref().map(|_| "Fn(..)"))
+            .field("error_kind", &self.error_kind)
+            .field("error_message", &self.error_message)
+            .field("responses", &self.responses)
+            .finish()
+    }
 }
 
 /// A record of an executed command.
@@ -39,12 +127,45 @@ pub struct MockDefinition {
 pub struct ExecutedCommand {
     pub program: OsString,
     pub args: Vec<OsString>,
+    pub current_dir: Option<OsString>,
+    pub env: Vec<(OsString, OsString)>,
+    pub stdin: Vec<u8>,
 }
 
 pub struct Command {
     inner: process::Command,
     program: OsString,
     args: Vec<OsString>,
+    current_dir: Option<OsString>,
+    env: Vec<(OsString, OsString)>,
+    stdin_data: Vec<u8>,
+}
+
+/// A fake handle to a "spawned" mocked command, mirroring
+/// [`std::process::Child`]'s `wait`/`wait_with_output` surface.
+pub struct Child {
+    result: Option<Result<process::Output>>,
+}
+
+#[cfg(test)]
+impl Child {
+    pub fn wait(&mut self) -> Result<process::ExitStatus> {
+        match self.result.take() {
+            Some(Ok(output)) => Ok(output.status),
+            Some(Err(err)) => Err(err),
+            None => Ok(exit_code(0)),
+        }
+    }
+
+    pub fn wait_with_output(mut self) -> Result<process::Output> {
+        self.result.take().unwrap_or_else(|| {
+            Ok(process::Output {
+                status: exit_code(0),
+                stdout: Vec::new(),
+                stderr: Vec::new(),
+            })
+        })
+    }
 }
 
 #[cfg(test)]
@@ -55,9 +176,19 @@ impl Command {
             inner: process::Command::new(&prog),
             program: prog,
             args: Vec::new(),
+            current_dir: None,
+            env: Vec::new(),
+            stdin_data: Vec::new(),
         }
     }
 
+    /// Feeds bytes to the mock as if they had been written to the child's
+    /// stdin, so a `with_response` closure can observe them.
+    pub fn stdin_data<S: Into<Vec<u8>>>(&mut self, data: S) -> &mut Self {
+        self.stdin_data = data.into();
+        self
+    }
+
     pub fn arg<S: AsRef<OsStr>>(&mut self, arg: S) -> &mut Self {
         let arg_os = arg.as_ref().to_os_string();
         self.args.push(arg_os.clone());
@@ -76,27 +207,117 @@ impl Command {
         self
     }
 
+    pub fn current_dir<P: AsRef<OsStr>>(&mut self, dir: P) -> &mut Self {
+        let dir_os = dir.as_ref().to_os_string();
+        self.current_dir = Some(dir_os.clone());
+        self.inner.current_dir(dir_os);
+        self
+    }
+
+    pub fn env<K: AsRef<OsStr>, V: AsRef<OsStr>>(&mut self, key: K, val: V) -> &mut Self {
+        let key_os = key.as_ref().to_os_string();
+        let val_os = val.as_ref().to_os_string();
+        self.env.retain(|(k, _)| k != &key_os);
+        self.env.push((key_os.clone(), val_os.clone()));
+        self.inner.env(key_os, val_os);
+        self
+    }
+
+    pub fn envs<I, K, V>(&mut self, vars: I) -> &mut Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: AsRef<OsStr>,
+        V: AsRef<OsStr>,
+    {
+        for (k, v) in vars {
+            self.env(k, v);
+        }
+        self
+    }
+
+    pub fn env_clear(&mut self) -> &mut Self {
+        self.env.clear();
+        self.inner.env_clear();
+        self
+    }
+
+    pub fn env_remove<K: AsRef<OsStr>>(&mut self, key: K) -> &mut Self {
+        let key_os = key.as_ref().to_os_string();
+        self.env.retain(|(k, _)| k != &key_os);
+        self.inner.env_remove(key_os);
+        self
+    }
+
+    pub fn stdin<T: Into<process::Stdio>>(&mut self, cfg: T) -> &mut Self {
+        self.inner.stdin(cfg);
+        self
+    }
+
+    pub fn stdout<T: Into<process::Stdio>>(&mut self, cfg: T) -> &mut Self {
+        self.inner.stdout(cfg);
+        self
+    }
+
+    pub fn stderr<T: Into<process::Stdio>>(&mut self, cfg: T) -> &mut Self {
+        self.inner.stderr(cfg);
+        self
+    }
+
     pub fn output(&mut self) -> Result<process::Output> {
+        self.resolve()
+    }
+
+    pub fn status(&mut self) -> Result<process::ExitStatus> {
+        self.resolve().map(|output| output.status)
+    }
+
+    pub fn spawn(&mut self) -> Result<Child> {
+        match self.resolve() {
+            Ok(output) => Ok(Child {
+                result: Some(Ok(output)),
+            }),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn resolve(&mut self) -> Result<process::Output> {
         // Record this command invocation
-        record_executed_command(&self.program, &self.args);
-
-        let (exit_status, stdout, stderr) = if let Some(mock) = find_mock(&self.program, &self.args)
-        {
-            let exit_status = mock.exit_status.unwrap_or(0);
-
-            (
-                exit_status,
-                mock.stdout.unwrap_or_default(),
-                mock.stderr.unwrap_or_default(),
-            )
-        } else {
-            (0, "".into(), "".into())
+        record_executed_command(
+            &self.program,
+            &self.args,
+            self.current_dir.as_ref(),
+            &self.env,
+            &self.stdin_data,
+        );
+
+        let Some(mock) = find_mock(
+            &self.program,
+            &self.args,
+            self.current_dir.as_ref(),
+            &self.env,
+        ) else {
+            return Ok(process::Output {
+                status: exit_code(0),
+                stdout: Vec::new(),
+                stderr: Vec::new(),
+            });
         };
 
+        if let Some(kind) = mock.error_kind {
+            return Err(match mock.error_message {
+                Some(message) => io::Error::new(kind, message),
+                None => io::Error::from(kind),
+            });
+        }
+
+        if let Some(response) = &mock.response {
+            return response(&self.args, &self.stdin_data);
+        }
+
         Ok(process::Output {
-            status: exit_code(exit_status),
-            stdout,
-            stderr,
+            status: exit_code(mock.exit_status.unwrap_or(0)),
+            stdout: mock.stdout.unwrap_or_default(),
+            stderr: mock.stderr.unwrap_or_default(),
         })
     }
 }
@@ -115,17 +336,19 @@ fn exit_code(code: i32) -> process::ExitStatus {
     }
 }
 
-fn record_executed_command<I, S>(program: &OsStr, args: I)
-where
-    I: IntoIterator<Item = S>,
-    S: AsRef<OsStr>,
-{
+fn record_executed_command(
+    program: &OsStr,
+    args: &[OsString],
+    current_dir: Option<&OsString>,
+    env: &[(OsString, OsString)],
+    stdin: &[u8],
+) {
     let cmd = ExecutedCommand {
         program: program.to_owned(),
-        args: args
-            .into_iter()
-            .map(|arg| arg.as_ref().to_owned())
-            .collect(),
+        args: args.to_vec(),
+        current_dir: current_dir.cloned(),
+        env: env.to_vec(),
+        stdin: stdin.to_vec(),
     };
     EXECUTED_COMMANDS.lock().unwrap().push(cmd);
 }
@@ -136,8 +359,23 @@ pub fn get_executed_commands() -> Vec<ExecutedCommand> {
     EXECUTED_COMMANDS.lock().unwrap().clone()
 }
 
+#[cfg(test)]
+/// Clears the log of executed commands, so state doesn't leak between tests.
+pub fn reset_executed_commands() {
+    EXECUTED_COMMANDS.lock().unwrap().clear();
+}
+
+#[cfg(test)]
+/// Clears all registered mocks, so state doesn't leak between tests.
+pub fn reset_mocks() {
+    MOCK_REGISTRY.lock().unwrap().clear();
+}
+
 impl CommandMockBuilder {
     pub fn register(self) {
-        MOCK_REGISTRY.lock().unwrap().push(self.build());
+        MOCK_REGISTRY.lock().unwrap().push(RegisteredMock {
+            definition: self.build(),
+            call_count: AtomicUsize::new(0),
+        });
     }
 }
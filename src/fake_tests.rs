@@ -1,8 +1,25 @@
 mod tests {
-    use crate::{was_command_executed, Command};
+    use crate::{
+        assert_executed_in_order, reset_executed_commands, reset_mocks, times_executed,
+        was_command_executed, Command,
+    };
+    use std::sync::Mutex;
+
+    /// Serializes every test in this module, since they all register mocks
+    /// and/or assert against the process-wide `MOCK_REGISTRY`/
+    /// `EXECUTED_COMMANDS` registries and Rust runs tests on multiple
+    /// threads by default.
+    static GLOBAL_STATE_LOCK: Mutex<()> = Mutex::new(());
+
+    fn lock_global_state() -> std::sync::MutexGuard<'static, ()> {
+        GLOBAL_STATE_LOCK
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
 
     #[test]
     fn using_test_code_unmocked() {
+        let _guard = lock_global_state();
         let output = Command::new("echo")
             .arg("hello")
             .current_dir("$HOME")
@@ -19,6 +36,7 @@ mod tests {
 
     #[test]
     fn using_test_code_mocked() {
+        let _guard = lock_global_state();
         crate::mock("echo")
             .with_arg("world")
             .with_status(1)
@@ -38,6 +56,7 @@ mod tests {
 
     #[test]
     fn git_mocks() {
+        let _guard = lock_global_state();
         crate::mock("git")
             .with_arg("push")
             .with_stdout(b"Everything up-to-date")
@@ -71,8 +90,213 @@ mod tests {
         assert!(!was_command_executed(&["git", "push", "--force"], None));
     }
 
+    #[test]
+    fn dynamic_response_sees_args_and_stdin() {
+        let _guard = lock_global_state();
+        crate::mock("wc")
+            .with_arg("-l")
+            .with_response(|_args, stdin| {
+                use std::os::unix::process::ExitStatusExt;
+
+                let lines = stdin.iter().filter(|&&b| b == b'\n').count();
+                Ok(std::process::Output {
+                    status: std::process::ExitStatus::from_raw(0),
+                    stdout: lines.to_string().into_bytes(),
+                    stderr: Vec::new(),
+                })
+            })
+            .register();
+
+        let mut cmd = Command::new("wc");
+        cmd.arg("-l");
+        cmd.stdin_data(b"one\ntwo\nthree\n".to_vec());
+        let output = cmd.output().unwrap();
+        assert_eq!(output.stdout, b"3");
+
+        assert!(was_command_executed(&["wc", "-l"], None));
+    }
+
+    #[test]
+    fn wildcard_and_regex_arg_matchers() {
+        let _guard = lock_global_state();
+        crate::mock("cp")
+            .with_any_arg()
+            .with_arg_matching(r"^/tmp/.+\.tmp$")
+            .with_stdout("copied")
+            .register();
+
+        crate::mock("docker")
+            .with_arg("run")
+            .with_rest()
+            .with_stdout("ran")
+            .register();
+
+        let output = Command::new("cp")
+            .arg("source.txt")
+            .arg("/tmp/a1b2c3.tmp")
+            .output()
+            .unwrap();
+        assert_eq!(output.stdout, b"copied");
+
+        let output = Command::new("docker")
+            .arg("run")
+            .arg("--rm")
+            .arg("alpine")
+            .output()
+            .unwrap();
+        assert_eq!(output.stdout, b"ran");
+
+        assert!(was_command_executed(
+            &["cp", "source.txt", "/tmp/a1b2c3.tmp"],
+            None
+        ));
+    }
+
+    #[test]
+    fn with_error_returns_err_instead_of_output() {
+        let _guard = lock_global_state();
+        crate::mock("ghost-binary")
+            .with_error(std::io::ErrorKind::NotFound)
+            .with_error_message("No such file or directory (os error 2)")
+            .register();
+
+        let err = Command::new("ghost-binary").output().unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+        assert_eq!(err.to_string(), "No such file or directory (os error 2)");
+    }
+
+    #[test]
+    fn spawn_returns_err_instead_of_a_child() {
+        let _guard = lock_global_state();
+        crate::mock("ghost-binary")
+            .with_arg("spawn")
+            .with_error(std::io::ErrorKind::NotFound)
+            .with_error_message("No such file or directory (os error 2)")
+            .register();
+
+        let err = Command::new("ghost-binary").arg("spawn").spawn().unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+        assert_eq!(err.to_string(), "No such file or directory (os error 2)");
+    }
+
+    #[test]
+    fn status_and_spawn_replay_the_mock() {
+        let _guard = lock_global_state();
+        crate::mock("make")
+            .with_arg("build")
+            .with_status(2)
+            .with_stderr("build failed")
+            .register();
+
+        let status = Command::new("make").arg("build").status().unwrap();
+        assert!(!status.success());
+
+        let mut child = Command::new("make").arg("build").spawn().unwrap();
+        let status = child.wait().unwrap();
+        assert!(!status.success());
+
+        let output = Command::new("make")
+            .arg("build")
+            .spawn()
+            .unwrap()
+            .wait_with_output()
+            .unwrap();
+        assert_eq!(output.stderr, b"build failed");
+    }
+
+    #[test]
+    fn current_dir_and_env_narrow_the_match() {
+        let _guard = lock_global_state();
+        crate::mock("printenv")
+            .current_dir("/srv/app")
+            .with_env("STAGE", "prod")
+            .with_arg("STAGE")
+            .with_stdout("prod")
+            .register();
+
+        let output = Command::new("printenv")
+            .current_dir("/srv/app")
+            .env("STAGE", "prod")
+            .arg("STAGE")
+            .output()
+            .unwrap();
+        assert_eq!(output.stdout, b"prod");
+
+        // Different current_dir: no mock applies, so output is empty.
+        let output = Command::new("printenv")
+            .current_dir("/tmp")
+            .env("STAGE", "prod")
+            .arg("STAGE")
+            .output()
+            .unwrap();
+        assert!(output.stdout.is_empty());
+    }
+
+    #[test]
+    fn times_executed_and_order_are_tracked() {
+        let _guard = lock_global_state();
+        reset_executed_commands();
+        reset_mocks();
+
+        crate::mock("git").with_arg("add").with_rest().register();
+        crate::mock("git").with_arg("commit").with_rest().register();
+        crate::mock("git").with_arg("push").register();
+
+        Command::new("git").arg("add").arg(".").output().unwrap();
+        Command::new("git")
+            .arg("commit")
+            .arg("-m")
+            .arg("wip")
+            .output()
+            .unwrap();
+        Command::new("git").arg("add").arg("README.md").output().unwrap();
+        Command::new("git").arg("push").output().unwrap();
+
+        assert_eq!(times_executed(&["git", "add", "."], None), 1);
+        assert_eq!(
+            times_executed(&["git", "add", "README.md"], None),
+            1
+        );
+
+        assert_executed_in_order(&[
+            &["git", "add", "."],
+            &["git", "commit", "-m", "wip"],
+            &["git", "push"],
+        ]);
+
+        reset_executed_commands();
+        assert_eq!(times_executed(&["git", "push"], None), 0);
+    }
+
+    #[test]
+    fn sequential_responses_replay_in_call_order() {
+        let _guard = lock_global_state();
+        crate::mock("git")
+            .with_arg("status")
+            .then_stdout("On branch main\nChanges not staged for commit")
+            .then_stdout("On branch main\nnothing to commit, working tree clean")
+            .register();
+
+        let first = Command::new("git").arg("status").output().unwrap();
+        assert_eq!(
+            first.stdout,
+            b"On branch main\nChanges not staged for commit"
+        );
+
+        let second = Command::new("git").arg("status").output().unwrap();
+        assert_eq!(
+            second.stdout,
+            b"On branch main\nnothing to commit, working tree clean"
+        );
+
+        // The queue is exhausted, so further calls repeat the last entry.
+        let third = Command::new("git").arg("status").output().unwrap();
+        assert_eq!(third.stdout, second.stdout);
+    }
+
     #[test]
     fn file_etc_mock() {
+        let _guard = lock_global_state();
         crate::mock("file")
             .current_dir("/")
             .with_arg("etc")
@@ -12,6 +12,7 @@
 //! - Mock command execution with specific arguments
 //! - Set custom exit codes, stdout, and stderr
 //! - Verify command execution happened with specific arguments
+//! - Compare output against wildcard-tolerant templates, not exact bytes
 //! - Automatically disabled outside of test mode
 //!
 //! ## Usage Example
@@ -80,6 +81,10 @@
 //! Your existing code will continue to work exactly as before, but now you can add mocks in your tests.
 
 use std::ffi::OsString;
+use std::process;
+use std::sync::Arc;
+
+use regex::Regex;
 
 #[cfg(not(feature = "test"))]
 mod real;
@@ -93,6 +98,13 @@ mod fake_tests;
 #[cfg(feature = "test")]
 pub use fake::*;
 
+#[cfg(feature = "test")]
+mod assertions;
+#[cfg(feature = "test")]
+mod assertions_tests;
+#[cfg(feature = "test")]
+pub use assertions::*;
+
 #[cfg(feature = "test")]
 #[track_caller]
 /// Panics if `pieces` is empty.
@@ -101,7 +113,7 @@ pub fn was_command_executed(pieces: &[&str], current_dir: Option<&str>) -> bool
 
     let (program_os, args_os) = pieces.split_first().unwrap();
 
-    let dir = current_dir.map(|s| OsString::from(s));
+    let dir = current_dir.map(OsString::from);
 
     get_executed_commands().iter().any(|cmd| {
         cmd.program == OsStr::new(program_os) && cmd.args == args_os && cmd.current_dir == dir
@@ -115,6 +127,67 @@ pub fn was_command_executed(_pieces: &[&str], _current_dir: Option<&str>) -> boo
     panic!("called outside of `cfg(test)` context");
 }
 
+#[cfg(feature = "test")]
+#[track_caller]
+/// Returns how many times a command with these exact arguments was executed.
+///
+/// Panics if `pieces` is empty.
+pub fn times_executed(pieces: &[&str], current_dir: Option<&str>) -> usize {
+    use std::ffi::OsStr;
+
+    let (program_os, args_os) = pieces.split_first().unwrap();
+
+    let dir = current_dir.map(OsString::from);
+
+    get_executed_commands()
+        .iter()
+        .filter(|cmd| {
+            cmd.program == OsStr::new(program_os) && cmd.args == args_os && cmd.current_dir == dir
+        })
+        .count()
+}
+
+#[cfg(not(feature = "test"))]
+#[track_caller]
+/// Panics if `pieces` is empty.
+pub fn times_executed(_pieces: &[&str], _current_dir: Option<&str>) -> usize {
+    panic!("called outside of `cfg(test)` context");
+}
+
+#[cfg(feature = "test")]
+#[track_caller]
+/// Asserts that each command in `sequence` was executed in the given order.
+///
+/// The commands need not be contiguous in the execution log; only their
+/// relative order is checked, not their working directory.
+pub fn assert_executed_in_order(sequence: &[&[&str]]) {
+    use std::ffi::OsStr;
+
+    let commands = get_executed_commands();
+    let mut cursor = 0;
+
+    for pieces in sequence {
+        let (program_os, args_os) = pieces.split_first().expect("each command needs a program");
+
+        let found = commands[cursor..]
+            .iter()
+            .position(|cmd| cmd.program == OsStr::new(program_os) && cmd.args == args_os);
+
+        match found {
+            Some(offset) => cursor += offset + 1,
+            None => panic!(
+                "expected {pieces:?} to execute after position {cursor}, but it never did in that order"
+            ),
+        }
+    }
+}
+
+#[cfg(not(feature = "test"))]
+#[track_caller]
+pub fn assert_executed_in_order(_sequence: &[&[&str]]) {
+    panic!("called outside of `cfg(test)` context");
+}
+
 /// Creates a new command mock builder for the specified program.
 ///
 /// This function is the entry point for defining mocked command behavior in tests.
@@ -154,14 +227,55 @@ pub fn mock<S: Into<OsString>>(program: S) -> CommandMockBuilder {
     CommandMockBuilder::new(program)
 }
 
+/// A closure that computes a command's [`process::Output`] from the arguments
+/// it was actually invoked with and any bytes written to its stdin.
+///
+/// Registered via [`CommandMockBuilder::with_response`] for mocks whose
+/// output depends on the call, rather than being fixed up front.
+pub type MockResponseFn =
+    Arc<dyn Fn(&[OsString], &[u8]) -> std::io::Result<process::Output> + Send + Sync>;
+
+/// How a single recorded argument is matched against a mock.
+///
+/// A mock's argument list is a sequence of matchers rather than literal
+/// strings, so commands with volatile arguments (temp paths, hashes,
+/// timestamps) can still be mocked precisely where it matters.
+#[derive(Debug, Clone)]
+pub enum ArgMatcher {
+    /// Matches exactly one argument equal to this value.
+    Exact(OsString),
+    /// Matches exactly one argument, whatever its value.
+    Any,
+    /// Matches exactly one argument whose UTF-8 value satisfies the regex.
+    Regex(Regex),
+    /// Matches zero or more remaining arguments. Must be the last matcher.
+    Rest,
+}
+
+/// One response in a mock's queue of successive, call-ordered responses.
+///
+/// Any field left as `None` falls back to the mock's base
+/// `with_stdout`/`with_stderr`/`with_status` value.
+#[derive(Debug, Clone, Default)]
+pub struct QueuedResponse {
+    pub exit_status: Option<i32>,
+    pub stdout: Option<Vec<u8>>,
+    pub stderr: Option<Vec<u8>>,
+}
+
 pub struct CommandMockBuilder {
     #[allow(unused)] // actually used when `cfg(test)`
     program: OsString,
-    args: Vec<OsString>,
+    args: Vec<ArgMatcher>,
     current_dir: Option<OsString>,
+    env: Vec<(OsString, OsString)>,
     exit_status: Option<i32>,
     stdout: Option<Vec<u8>>,
     stderr: Option<Vec<u8>>,
+    response: Option<MockResponseFn>,
+    error_kind: Option<std::io::ErrorKind>,
+    error_message: Option<String>,
+    responses: Vec<QueuedResponse>,
 }
 
 impl CommandMockBuilder {
@@ -171,9 +285,14 @@ impl CommandMockBuilder {
             program: program.into(),
             args: Vec::new(),
             current_dir: None,
+            env: Vec::new(),
             exit_status: None,
             stdout: None,
             stderr: None,
+            response: None,
+            error_kind: None,
+            error_message: None,
+            responses: Vec::new(),
         }
     }
 
@@ -183,27 +302,86 @@ impl CommandMockBuilder {
         self
     }
 
+    /// Requires the command to have this environment variable set to this
+    /// value. Only constrains matching when at least one is configured.
+    pub fn with_env<K: Into<OsString>, V: Into<OsString>>(mut self, key: K, val: V) -> Self {
+        self.env.push((key.into(), val.into()));
+        self
+    }
+
     /// Sets the command arguments.
     ///
-    /// This replaces any existing arguments with the provided ones.
+    /// This replaces any existing arguments with the provided ones. Each
+    /// argument must match exactly; use `with_any_arg`, `with_arg_matching`,
+    /// or `with_rest` for more permissive matching.
     pub fn with_args<I, S>(mut self, args: I) -> Self
     where
         I: IntoIterator<Item = S>,
         S: Into<OsString>,
     {
-        self.args = args.into_iter().map(Into::into).collect();
+        self.args = args.into_iter().map(Into::into).map(ArgMatcher::Exact).collect();
         self
     }
 
-    /// Adds a single argument to the command.
+    /// Adds a single argument to the command that must match exactly.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `with_rest` was already called, since `Rest` must be the
+    /// last matcher.
     pub fn with_arg<S>(mut self, arg: S) -> Self
     where
         S: Into<OsString>,
     {
-        self.args.push(arg.into());
+        self.push_arg_matcher(ArgMatcher::Exact(arg.into()));
+        self
+    }
+
+    /// Adds a matcher that accepts exactly one argument, whatever its value.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `with_rest` was already called, since `Rest` must be the
+    /// last matcher.
+    pub fn with_any_arg(mut self) -> Self {
+        self.push_arg_matcher(ArgMatcher::Any);
+        self
+    }
+
+    /// Adds a matcher that accepts exactly one argument matching `pattern`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pattern` is not a valid regex, or if `with_rest` was
+    /// already called, since `Rest` must be the last matcher.
+    pub fn with_arg_matching<S: AsRef<str>>(mut self, pattern: S) -> Self {
+        let matcher =
+            ArgMatcher::Regex(Regex::new(pattern.as_ref()).expect("invalid mock argument regex"));
+        self.push_arg_matcher(matcher);
+        self
+    }
+
+    /// Adds a terminal matcher that accepts zero or more remaining arguments.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `with_rest` was already called, since it can only appear
+    /// once, as the last matcher.
+    pub fn with_rest(mut self) -> Self {
+        self.push_arg_matcher(ArgMatcher::Rest);
         self
     }
 
+    /// Pushes an argument matcher, rejecting any matcher added after a
+    /// `Rest`, which must stay the last one in the list.
+    fn push_arg_matcher(&mut self, matcher: ArgMatcher) {
+        assert!(
+            !matches!(self.args.last(), Some(ArgMatcher::Rest)),
+            "with_rest() must be the last argument matcher"
+        );
+        self.args.push(matcher);
+    }
+
     /// Sets the expected exit status.
     pub fn with_status(mut self, status: i32) -> Self {
         self.exit_status = Some(status);
@@ -222,6 +400,60 @@ impl CommandMockBuilder {
         self
     }
 
+    /// Computes the output dynamically from the actual arguments and any
+    /// bytes written to stdin, instead of returning a fixed response.
+    ///
+    /// This takes precedence over `with_stdout`/`with_stderr`/`with_status`
+    /// when both are set on the same mock.
+    pub fn with_response<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&[OsString], &[u8]) -> std::io::Result<process::Output> + Send + Sync + 'static,
+    {
+        self.response = Some(Arc::new(f));
+        self
+    }
+
+    /// Makes the mocked command fail with the given I/O error kind instead
+    /// of returning output, so callers can exercise error paths like a
+    /// missing binary (`ErrorKind::NotFound`) or permission denial.
+    pub fn with_error(mut self, kind: std::io::ErrorKind) -> Self {
+        self.error_kind = Some(kind);
+        self
+    }
+
+    /// Sets a custom message for the error configured via `with_error`.
+    pub fn with_error_message<S: Into<String>>(mut self, message: S) -> Self {
+        self.error_message = Some(message.into());
+        self
+    }
+
+    /// Appends a response returned on the next call after all previously
+    /// queued responses have been used, modeling state transitions across
+    /// repeated invocations (e.g. `git status` before and after a commit).
+    ///
+    /// Once the queue is exhausted, the last entry is reused for every
+    /// further call.
+    pub fn then_response(mut self, response: QueuedResponse) -> Self {
+        self.responses.push(response);
+        self
+    }
+
+    /// Appends a queued response with only `stdout` set.
+    pub fn then_stdout<S: Into<Vec<u8>>>(self, stdout: S) -> Self {
+        self.then_response(QueuedResponse {
+            stdout: Some(stdout.into()),
+            ..Default::default()
+        })
+    }
+
+    /// Appends a queued response with only the exit status set.
+    pub fn then_status(self, status: i32) -> Self {
+        self.then_response(QueuedResponse {
+            exit_status: Some(status),
+            ..Default::default()
+        })
+    }
+
     #[cfg(feature = "test")]
     /// Consumes the builder, returning a `MockDefinition`.
     pub fn build(self) -> MockDefinition {
@@ -229,9 +461,14 @@ impl CommandMockBuilder {
             program: self.program,
             args: self.args,
             current_dir: self.current_dir,
+            env: self.env,
             exit_status: self.exit_status,
             stdout: self.stdout,
             stderr: self.stderr,
+            response: self.response,
+            error_kind: self.error_kind,
+            error_message: self.error_message,
+            responses: self.responses,
         }
     }
 